@@ -0,0 +1,11 @@
+pub mod request;
+
+pub use request::*;
+
+pub static DEFAULT_SYSTEM_TEMPLATE: &str = r#"You are a function calling AI agent with access to the following tools:
+
+{tools}
+
+When a tool is needed to answer the user, respond with a `tool_use` content block containing a
+unique `id`, the tool's `name`, and an `input` object matching its schema. Otherwise, answer
+directly in plain text."#;
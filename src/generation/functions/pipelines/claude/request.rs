@@ -0,0 +1,310 @@
+use crate::error::OllamaError;
+use crate::generation::chat::{ChatMessage, ChatMessageResponse};
+use crate::generation::functions::pipelines::claude::DEFAULT_SYSTEM_TEMPLATE;
+use crate::generation::functions::pipelines::{
+    run_tool_calls, validate_tool_arguments, RequestParserBase, ToolCallAttempt, ToolChoice,
+};
+use crate::generation::functions::tools::Tool;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// One block of an Anthropic-style `content` array. `Text` blocks are model prose; `ToolUse`
+/// blocks carry a tool invocation, correlated back to its result via `id`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeContentBlock {
+    Text { text: String },
+    ToolUse { id: String, name: String, input: Value },
+}
+
+/// A single resolved `tool_use` block, carrying the id the model assigned it so the
+/// corresponding `tool_result` can reference it.
+#[derive(Debug, Clone)]
+pub struct ClaudeToolUse {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+}
+
+pub fn convert_to_claude_tool(tool: &Arc<dyn Tool>) -> Value {
+    json!({
+        "name": tool.name(),
+        "description": tool.description(),
+        "input_schema": tool.parameters(),
+    })
+}
+
+pub struct ClaudeFunctionCall {}
+
+impl Default for ClaudeFunctionCall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClaudeFunctionCall {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub async fn function_call_with_history(
+        &self,
+        model_name: String,
+        tool_use: ClaudeToolUse,
+        tool: Arc<dyn Tool>,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse> {
+        let result = tool.run(tool_use.input).await;
+        match result {
+            Ok(result) => Ok(ChatMessageResponse {
+                model: model_name.clone(),
+                created_at: "".to_string(),
+                message: Some(ChatMessage::assistant(
+                    self.format_tool_result(&tool_use.id, &result),
+                )),
+                done: true,
+                final_data: None,
+            }),
+            Err(e) => Err(self.error_handler(OllamaError::from(e))),
+        }
+    }
+
+    pub fn format_tool_result(&self, tool_use_id: &str, result: &str) -> String {
+        format!(
+            "<tool_result tool_use_id=\"{}\">\n{}\n</tool_result>\n",
+            tool_use_id, result
+        )
+    }
+
+    fn clean_response(&self, json_str: &str) -> String {
+        json_str
+            .trim()
+            .trim_start_matches("```json")
+            .trim_end_matches("```")
+            .trim()
+            .to_string()
+    }
+
+    /// Extracts every `tool_use` block from a response, whether the model emitted a single
+    /// content block or a full `content` array mixing `text` and `tool_use` blocks.
+    pub fn extract_tool_use_blocks(&self, content: &str) -> Vec<ClaudeToolUse> {
+        let cleaned = self.clean_response(content);
+
+        let blocks = match serde_json::from_str::<Vec<ClaudeContentBlock>>(&cleaned) {
+            Ok(blocks) => blocks,
+            Err(_) => match serde_json::from_str::<ClaudeContentBlock>(&cleaned) {
+                Ok(block) => vec![block],
+                Err(_) => return Vec::new(),
+            },
+        };
+
+        blocks
+            .into_iter()
+            .filter_map(|block| match block {
+                ClaudeContentBlock::ToolUse { id, name, input } => {
+                    Some(ClaudeToolUse { id, name, input })
+                }
+                ClaudeContentBlock::Text { .. } => None,
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RequestParserBase for ClaudeFunctionCall {
+    async fn parse(
+        &self,
+        input: &str,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse> {
+        let tool_uses = self.extract_tool_use_blocks(input);
+        if tool_uses.is_empty() {
+            return Err(self.error_handler(OllamaError::from(
+                "No tool_use block found in the response".to_string(),
+            )));
+        }
+
+        if *tool_choice == ToolChoice::None {
+            return Err(self.error_handler(OllamaError::from(
+                "Tool calls are disabled, but the model attempted one.".to_string(),
+            )));
+        }
+
+        let mut attempts: Vec<ToolCallAttempt<String>> = Vec::with_capacity(tool_uses.len());
+        for tool_use in tool_uses {
+            if let ToolChoice::Required(required_name) = tool_choice {
+                if tool_use.name != *required_name {
+                    return Err(self.error_handler(OllamaError::from(format!(
+                        "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                        required_name, tool_use.name, required_name
+                    ))));
+                }
+            }
+            match tools.iter().find(|t| t.name() == tool_use.name) {
+                Some(tool) => match validate_tool_arguments(&tool.parameters(), &tool_use.input) {
+                    Ok(()) => attempts.push(ToolCallAttempt::Ready(
+                        tool_use.id,
+                        tool.clone(),
+                        tool_use.input,
+                    )),
+                    Err(reason) => attempts.push(ToolCallAttempt::Failed(
+                        tool_use.id,
+                        format!(
+                            "Arguments for '{}' do not match its schema: {}",
+                            tool_use.name, reason
+                        ),
+                    )),
+                },
+                None => attempts.push(ToolCallAttempt::Failed(
+                    tool_use.id,
+                    format!("Tool not found: {}", tool_use.name),
+                )),
+            }
+        }
+
+        let combined = run_tool_calls(
+            attempts,
+            |id, value| self.format_tool_result(id, value),
+            |id, error| self.format_tool_result(id, &format!("Error: {}", error)),
+        )
+        .await;
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(combined)),
+            done: true,
+            final_data: None,
+        })
+    }
+
+    /// Buffers the entire stream before parsing anything. A Claude-style `content` array only
+    /// becomes valid JSON once every block has fully arrived, so there's no reliable block
+    /// boundary to finalize on until the stream ends — unlike `tiny_agent`'s `parse_stream`, this
+    /// provides no incremental assembly; it's equivalent to buffering the full response and
+    /// calling `parse`.
+    async fn parse_stream(
+        &self,
+        mut stream: Pin<Box<dyn Stream<Item = ChatMessageResponse> + Send>>,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse> {
+        let mut buffer = String::new();
+        while let Some(chunk) = stream.next().await {
+            if let Some(message) = &chunk.message {
+                buffer.push_str(&message.content);
+            }
+            if chunk.done {
+                break;
+            }
+        }
+
+        let tool_uses = self.extract_tool_use_blocks(&buffer);
+        if tool_uses.is_empty() {
+            return Err(self.error_handler(OllamaError::from(
+                "No tool_use block found in the response".to_string(),
+            )));
+        }
+
+        if *tool_choice == ToolChoice::None {
+            return Err(self.error_handler(OllamaError::from(
+                "Tool calls are disabled, but the model attempted one.".to_string(),
+            )));
+        }
+
+        let mut attempts: Vec<ToolCallAttempt<String>> = Vec::with_capacity(tool_uses.len());
+        for tool_use in tool_uses {
+            if let ToolChoice::Required(required_name) = tool_choice {
+                if tool_use.name != *required_name {
+                    return Err(self.error_handler(OllamaError::from(format!(
+                        "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                        required_name, tool_use.name, required_name
+                    ))));
+                }
+            }
+            match tools.iter().find(|t| t.name() == tool_use.name) {
+                Some(tool) => match validate_tool_arguments(&tool.parameters(), &tool_use.input) {
+                    Ok(()) => attempts.push(ToolCallAttempt::Ready(
+                        tool_use.id,
+                        tool.clone(),
+                        tool_use.input,
+                    )),
+                    Err(reason) => attempts.push(ToolCallAttempt::Failed(
+                        tool_use.id,
+                        format!(
+                            "Arguments for '{}' do not match its schema: {}",
+                            tool_use.name, reason
+                        ),
+                    )),
+                },
+                None => attempts.push(ToolCallAttempt::Failed(
+                    tool_use.id,
+                    format!("Tool not found: {}", tool_use.name),
+                )),
+            }
+        }
+
+        let combined = run_tool_calls(
+            attempts,
+            |id, value| self.format_tool_result(id, value),
+            |id, error| self.format_tool_result(id, &format!("Error: {}", error)),
+        )
+        .await;
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(combined)),
+            done: true,
+            final_data: None,
+        })
+    }
+
+    async fn get_system_message(&self, tools: &[Arc<dyn Tool>], tool_choice: &ToolChoice) -> ChatMessage {
+        if *tool_choice == ToolChoice::None {
+            return ChatMessage::system(
+                "Answer the user directly in plain text. Do not call any tools.".to_string(),
+            );
+        }
+
+        let tools_info: Vec<Value> = match tool_choice {
+            ToolChoice::Required(name) => tools
+                .iter()
+                .filter(|t| &t.name() == name)
+                .map(convert_to_claude_tool)
+                .collect(),
+            _ => tools.iter().map(convert_to_claude_tool).collect(),
+        };
+        let tools_json = serde_json::to_string(&tools_info).unwrap();
+        let mut system_message_content = DEFAULT_SYSTEM_TEMPLATE.replace("{tools}", &tools_json);
+        if let ToolChoice::Required(name) = tool_choice {
+            system_message_content.push_str(&format!(
+                "\nYou must call the '{}' tool to answer this request. No other tool is allowed.",
+                name
+            ));
+        }
+        ChatMessage::system(system_message_content)
+    }
+
+    fn error_handler(&self, error: OllamaError) -> ChatMessageResponse {
+        ChatMessageResponse {
+            model: "".to_string(),
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(error.to_string())),
+            done: true,
+            final_data: None,
+        }
+    }
+
+    fn has_tool_call_attempt(&self, input: &str) -> bool {
+        // A malformed block yields nothing from `extract_tool_use_blocks`, so a response that
+        // names the `tool_use` block type also counts as an attempt.
+        !self.extract_tool_use_blocks(input).is_empty() || input.contains("\"tool_use\"")
+    }
+}
@@ -0,0 +1,88 @@
+use crate::generation::chat::{ChatMessage, ChatMessageResponse};
+use crate::generation::functions::pipelines::{RequestParserBase, ToolChoice};
+use crate::generation::functions::tools::Tool;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Drives a [`RequestParserBase`] through repeated parse-and-run rounds, feeding each tool's
+/// result back into the message history and re-prompting the model until it answers with no
+/// tool call or `max_iterations` is reached.
+///
+/// The pipelines in this module only do a single parse-and-dispatch round; `FunctionCallLoop`
+/// turns one of them into a true multi-step agent.
+pub struct FunctionCallLoop<P: RequestParserBase> {
+    parser: P,
+    model_name: String,
+    tools: Vec<Arc<dyn Tool>>,
+    max_iterations: usize,
+}
+
+impl<P: RequestParserBase> FunctionCallLoop<P> {
+    pub fn new(
+        parser: P,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        max_iterations: usize,
+    ) -> Self {
+        Self {
+            parser,
+            model_name,
+            tools,
+            max_iterations,
+        }
+    }
+
+    /// Runs the loop starting from `messages`. `send` takes the current message history and
+    /// returns the model's raw text response for that turn.
+    ///
+    /// Returns the full intermediate message trace (including every assistant turn and tool
+    /// response appended along the way) so callers can inspect each step.
+    pub async fn run<F, Fut>(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        tool_choice: ToolChoice,
+        mut send: F,
+    ) -> Result<Vec<ChatMessage>, ChatMessageResponse>
+    where
+        F: FnMut(Vec<ChatMessage>) -> Fut,
+        Fut: Future<Output = Result<String, ChatMessageResponse>>,
+    {
+        for _ in 0..self.max_iterations {
+            let raw_response = send(messages.clone()).await?;
+
+            // A response with no tool-call attempt at all is the model's final answer. One that
+            // attempted a call but failed to parse/validate/match `tool_choice` is not: it must
+            // loop back with the corrective `error_handler` feedback instead of ending here.
+            if !self.parser.has_tool_call_attempt(&raw_response) {
+                messages.push(ChatMessage::assistant(raw_response));
+                return Ok(messages);
+            }
+
+            messages.push(ChatMessage::assistant(raw_response.clone()));
+
+            match self
+                .parser
+                .parse(
+                    &raw_response,
+                    self.model_name.clone(),
+                    self.tools.clone(),
+                    &tool_choice,
+                )
+                .await
+            {
+                Ok(tool_result) => {
+                    if let Some(tool_message) = tool_result.message {
+                        messages.push(tool_message);
+                    }
+                }
+                Err(error_result) => {
+                    if let Some(error_message) = error_result.message {
+                        messages.push(error_message);
+                    }
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+}
@@ -1,12 +1,16 @@
 use crate::error::OllamaError;
 use crate::generation::chat::{ChatMessage, ChatMessageResponse};
 use crate::generation::functions::pipelines::openai::DEFAULT_SYSTEM_TEMPLATE;
-use crate::generation::functions::pipelines::RequestParserBase;
+use crate::generation::functions::pipelines::{
+    run_tool_calls, validate_tool_arguments, RequestParserBase, ToolCallAttempt, ToolChoice,
+};
 use crate::generation::functions::tools::Tool;
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::Arc;
 
 pub fn convert_to_openai_tool(tool: &Arc<dyn Tool>) -> Value {
@@ -80,35 +84,173 @@ impl RequestParserBase for GorillaFunctionCall {
         input: &str,
         model_name: String,
         tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
     ) -> Result<ChatMessageResponse, ChatMessageResponse> {
-        let response_value: Result<GorillaFunctionCallSignature, serde_json::Error> =
-            serde_json::from_str(&self.clean_tool_call(input));
-        match response_value {
-            Ok(response) => {
-                if let Some(tool) = tools.iter().find(|t| t.name() == response.name) {
-                    let tool_params = response.arguments;
-                    let result = self
-                        .function_call_with_history(
-                            model_name.clone(),
-                            tool_params.clone(),
-                            tool.clone(),
-                        )
-                        .await?;
-                    return Ok(result);
-                } else {
-                    return Err(self.error_handler(OllamaError::from("Tool not found".to_string())));
+        let cleaned = self.clean_tool_call(input);
+        // Gorilla emits either a single call object or, for parallel calls, a JSON array of them.
+        let calls: Vec<GorillaFunctionCallSignature> =
+            match serde_json::from_str::<Vec<GorillaFunctionCallSignature>>(&cleaned) {
+                Ok(calls) => calls,
+                Err(_) => match serde_json::from_str::<GorillaFunctionCallSignature>(&cleaned) {
+                    Ok(call) => vec![call],
+                    Err(e) => return Err(self.error_handler(OllamaError::from(e))),
+                },
+            };
+
+        if *tool_choice == ToolChoice::None {
+            return Err(self.error_handler(OllamaError::from(
+                "Tool calls are disabled, but the model attempted one.".to_string(),
+            )));
+        }
+
+        let mut attempts: Vec<ToolCallAttempt<()>> = Vec::with_capacity(calls.len());
+        for response in calls {
+            if let ToolChoice::Required(required_name) = tool_choice {
+                if &response.name != required_name {
+                    return Err(self.error_handler(OllamaError::from(format!(
+                        "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                        required_name, response.name, required_name
+                    ))));
                 }
             }
-            Err(e) => {
-                return Err(self.error_handler(OllamaError::from(e)));
+            match tools.iter().find(|t| t.name() == response.name) {
+                Some(tool) => match validate_tool_arguments(&tool.parameters(), &response.arguments) {
+                    Ok(()) => attempts.push(ToolCallAttempt::Ready((), tool.clone(), response.arguments)),
+                    Err(reason) => attempts.push(ToolCallAttempt::Failed(
+                        (),
+                        format!(
+                            "Arguments for '{}' do not match its schema: {}",
+                            response.name, reason
+                        ),
+                    )),
+                },
+                None => attempts.push(ToolCallAttempt::Failed(
+                    (),
+                    format!("Tool not found: {}", response.name),
+                )),
             }
         }
+
+        let combined = run_tool_calls(
+            attempts,
+            |_, value| value.to_string(),
+            |_, error| format!("Error calling tool: {}", error),
+        )
+        .await;
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(combined)),
+            done: true,
+            final_data: None,
+        })
     }
 
-    async fn get_system_message(&self, tools: &[Arc<dyn Tool>]) -> ChatMessage {
-        let tools_info: Vec<Value> = tools.iter().map(convert_to_openai_tool).collect();
+    /// Buffers the entire stream before parsing anything. Gorilla's call signature isn't
+    /// tag-delimited, so there's no reliable call boundary to finalize on until the whole object
+    /// (or array of objects) has arrived — unlike `tiny_agent`'s `parse_stream`, this provides no
+    /// incremental assembly; it's equivalent to buffering the full response and calling `parse`.
+    async fn parse_stream(
+        &self,
+        mut stream: Pin<Box<dyn Stream<Item = ChatMessageResponse> + Send>>,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse> {
+        let mut buffer = String::new();
+        while let Some(chunk) = stream.next().await {
+            if let Some(message) = &chunk.message {
+                buffer.push_str(&message.content);
+            }
+            if chunk.done {
+                break;
+            }
+        }
+
+        let cleaned = self.clean_tool_call(&buffer);
+        let calls: Vec<GorillaFunctionCallSignature> =
+            match serde_json::from_str::<Vec<GorillaFunctionCallSignature>>(&cleaned) {
+                Ok(calls) => calls,
+                Err(_) => match serde_json::from_str::<GorillaFunctionCallSignature>(&cleaned) {
+                    Ok(call) => vec![call],
+                    Err(e) => return Err(self.error_handler(OllamaError::from(e))),
+                },
+            };
+
+        if *tool_choice == ToolChoice::None {
+            return Err(self.error_handler(OllamaError::from(
+                "Tool calls are disabled, but the model attempted one.".to_string(),
+            )));
+        }
+
+        let mut attempts: Vec<ToolCallAttempt<()>> = Vec::with_capacity(calls.len());
+        for response in calls {
+            if let ToolChoice::Required(required_name) = tool_choice {
+                if &response.name != required_name {
+                    return Err(self.error_handler(OllamaError::from(format!(
+                        "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                        required_name, response.name, required_name
+                    ))));
+                }
+            }
+            match tools.iter().find(|t| t.name() == response.name) {
+                Some(tool) => match validate_tool_arguments(&tool.parameters(), &response.arguments) {
+                    Ok(()) => attempts.push(ToolCallAttempt::Ready((), tool.clone(), response.arguments)),
+                    Err(reason) => attempts.push(ToolCallAttempt::Failed(
+                        (),
+                        format!(
+                            "Arguments for '{}' do not match its schema: {}",
+                            response.name, reason
+                        ),
+                    )),
+                },
+                None => attempts.push(ToolCallAttempt::Failed(
+                    (),
+                    format!("Tool not found: {}", response.name),
+                )),
+            }
+        }
+
+        let combined = run_tool_calls(
+            attempts,
+            |_, value| value.to_string(),
+            |_, error| format!("Error calling tool: {}", error),
+        )
+        .await;
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(combined)),
+            done: true,
+            final_data: None,
+        })
+    }
+
+    async fn get_system_message(&self, tools: &[Arc<dyn Tool>], tool_choice: &ToolChoice) -> ChatMessage {
+        if *tool_choice == ToolChoice::None {
+            return ChatMessage::system(
+                "Answer the user directly in plain text. Do not call any tools.".to_string(),
+            );
+        }
+
+        let tools_info: Vec<Value> = match tool_choice {
+            ToolChoice::Required(name) => tools
+                .iter()
+                .filter(|t| &t.name() == name)
+                .map(convert_to_openai_tool)
+                .collect(),
+            _ => tools.iter().map(convert_to_openai_tool).collect(),
+        };
         let tools_json = serde_json::to_string(&tools_info).unwrap();
-        let system_message_content = DEFAULT_SYSTEM_TEMPLATE.replace("{tools}", &tools_json);
+        let mut system_message_content = DEFAULT_SYSTEM_TEMPLATE.replace("{tools}", &tools_json);
+        if let ToolChoice::Required(name) = tool_choice {
+            system_message_content.push_str(&format!(
+                "\nYou must call the '{}' tool to answer this request. No other tool is allowed.",
+                name
+            ));
+        }
         ChatMessage::system(system_message_content)
     }
 
@@ -121,4 +263,65 @@ impl RequestParserBase for GorillaFunctionCall {
             final_data: None,
         }
     }
+
+    fn has_tool_call_attempt(&self, input: &str) -> bool {
+        // Gorilla's call signature isn't tag-delimited, so a plain-text answer and an attempted
+        // (but malformed) call can only be told apart by whether it looks like JSON at all.
+        let cleaned = self.clean_tool_call(input);
+        cleaned.starts_with('{') || cleaned.starts_with('[')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::functions::tools::Tool;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> String {
+            "echo".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Echoes back the 'text' argument it's given".to_string()
+        }
+
+        fn parameters(&self) -> Value {
+            json!({
+                "type": "object",
+                "required": ["text"],
+                "properties": { "text": { "type": "string" } },
+            })
+        }
+
+        async fn run(&self, input: Value) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(input["text"].as_str().unwrap_or_default().to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_runs_remaining_calls_in_order_when_one_fails_validation() {
+        let gorilla = GorillaFunctionCall::new();
+        let tools: Vec<Arc<dyn Tool>> = vec![Arc::new(EchoTool)];
+        let input = r#"[
+            {"name": "echo", "arguments": {"oops": "bad"}},
+            {"name": "echo", "arguments": {"text": "second"}}
+        ]"#;
+
+        let response = gorilla
+            .parse(input, "test-model".to_string(), tools, &ToolChoice::Auto)
+            .await
+            .expect("a partially-failing batch should still produce a combined response");
+
+        let combined = response.message.expect("assistant message").content;
+        let lines: Vec<&str> = combined.lines().collect();
+        assert_eq!(lines.len(), 2, "{combined}");
+        assert!(lines[0].starts_with("Error calling tool:"), "{combined}");
+        assert_eq!(lines[1], "second");
+    }
 }
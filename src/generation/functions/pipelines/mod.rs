@@ -2,13 +2,40 @@ use crate::error::OllamaError;
 use crate::generation::chat::{ChatMessage, ChatMessageResponse};
 use crate::generation::functions::tools::Tool;
 use async_trait::async_trait;
+use futures::Stream;
+use serde_json::Value;
+use std::pin::Pin;
 use std::sync::Arc;
 
+pub mod claude;
+pub mod function_call_loop;
 pub mod nous_hermes;
 pub mod openai;
 pub mod tiny_agent;
 pub mod gorilla;
 
+/// Controls which, if any, tool a [`RequestParserBase`] implementation should steer the model
+/// towards calling.
+///
+/// Mirrors the `tool_choice` field exposed by OpenAI-style and TGI-style servers, letting callers
+/// get deterministic routing out of single-purpose agents instead of always leaving the decision
+/// up to the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// Let the model freely decide whether to call a tool, and which one.
+    Auto,
+    /// Suppress tool schemas entirely; the model must answer in plain text.
+    None,
+    /// Force the model to call the named tool and reject any other tool call.
+    Required(String),
+}
+
+impl Default for ToolChoice {
+    fn default() -> Self {
+        ToolChoice::Auto
+    }
+}
+
 #[async_trait]
 pub trait RequestParserBase {
     async fn parse(
@@ -16,6 +43,18 @@ pub trait RequestParserBase {
         input: &str,
         model_name: String,
         tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse>;
+    /// Consumes a stream of response chunks and dispatches the tool calls they contain. A tool
+    /// only runs once its arguments have been fully assembled and parsed. Formats with
+    /// tag-delimited calls (`tiny_agent`) finalize each call as soon as its closing tag arrives;
+    /// the others (`gorilla`, `claude`) buffer the whole stream and parse it once it ends.
+    async fn parse_stream(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = ChatMessageResponse> + Send>>,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
     ) -> Result<ChatMessageResponse, ChatMessageResponse>;
     fn format_query(&self, input: &str) -> String {
         input.to_string()
@@ -23,6 +62,205 @@ pub trait RequestParserBase {
     fn format_response(&self, response: &str) -> String {
         response.to_string()
     }
-    async fn get_system_message(&self, tools: &[Arc<dyn Tool>]) -> ChatMessage;
+    async fn get_system_message(&self, tools: &[Arc<dyn Tool>], tool_choice: &ToolChoice) -> ChatMessage;
     fn error_handler(&self, error: OllamaError) -> ChatMessageResponse;
+    /// Returns true if `input` looks like it contains an attempted tool call, valid or not.
+    ///
+    /// `parse` returns `Err` both when the model didn't try to call a tool at all and when an
+    /// attempted call can't be dispatched (it violates `tool_choice` or, depending on the format,
+    /// is malformed JSON); unknown tool names and arguments that fail schema validation are
+    /// reported inline in an `Ok` response instead. Callers that loop on `parse`'s result (e.g. `FunctionCallLoop`) need to tell
+    /// these apart: a genuine non-call means the model is done, while a failed attempt should be
+    /// retried with the corrective `error_handler` feedback instead of ending the conversation.
+    fn has_tool_call_attempt(&self, input: &str) -> bool;
+}
+
+/// Lightweight check that `arguments` satisfies a tool's declared parameter schema (the same
+/// `parameters` JSON returned by `Tool::parameters`) before it's ever dispatched: every
+/// `required` field is present, no undeclared properties are passed, and each declared
+/// property's JSON type matches. This isn't a full JSON-schema validator, just enough of a
+/// guarantee to keep malformed model output from reaching a tool.
+///
+/// Returns a human-readable description of every problem found, suitable for feeding back to the
+/// model so it can retry with corrected arguments.
+pub fn validate_tool_arguments(parameters: &Value, arguments: &Value) -> Result<(), String> {
+    let arguments_obj = match arguments.as_object() {
+        Some(obj) => obj,
+        None => return Err("arguments must be a JSON object".to_string()),
+    };
+
+    let required = parameters
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|fields| {
+            fields
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let missing: Vec<&str> = required
+        .into_iter()
+        .filter(|field| !arguments_obj.contains_key(*field))
+        .collect();
+    let mut problems = Vec::new();
+    if !missing.is_empty() {
+        problems.push(format!("missing required field(s): {}", missing.join(", ")));
+    }
+
+    if let Some(properties) = parameters.get("properties").and_then(Value::as_object) {
+        let mut unexpected = Vec::new();
+        let mut mismatched = Vec::new();
+        for (field, value) in arguments_obj {
+            match properties.get(field) {
+                Some(schema) => {
+                    if let Some(expected_type) = schema.get("type").and_then(Value::as_str) {
+                        if !json_type_matches(expected_type, value) {
+                            mismatched.push(format!("'{}' should be of type '{}'", field, expected_type));
+                        }
+                    }
+                }
+                None => unexpected.push(field.as_str()),
+            }
+        }
+        if !unexpected.is_empty() {
+            problems.push(format!("unexpected field(s): {}", unexpected.join(", ")));
+        }
+        if !mismatched.is_empty() {
+            problems.push(format!("invalid field(s): {}", mismatched.join(", ")));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+fn json_type_matches(expected_type: &str, value: &Value) -> bool {
+    match expected_type {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// One tool call a pipeline's `parse`/`parse_stream` has finished resolving, ready to hand to
+/// [`run_tool_calls`]: either it passed tool lookup and schema validation and is `Ready` to run,
+/// or it `Failed` synchronously (unknown tool name, schema mismatch) before ever reaching the
+/// tool.
+///
+/// `I` is whatever a pipeline's result format needs to correlate an outcome back to its call —
+/// `()` for formats with no per-call identifier (Gorilla, tiny_agent) or the model-assigned
+/// `tool_use` id for formats that require one (Claude).
+pub enum ToolCallAttempt<I> {
+    Ready(I, Arc<dyn Tool>, Value),
+    Failed(I, String),
+}
+
+/// Runs every `Ready` attempt in `attempts` concurrently and recombines all outcomes, `Failed`
+/// ones included, in their original order with a single newline join.
+///
+/// This is the dispatch shape shared by every pipeline: pre-allocate a slot per call, fill
+/// synchronous failures in immediately, run the rest concurrently via `join_all`, and back-fill
+/// their results by index — so a single bad call never discards the rest of the batch. Callers
+/// supply `on_success`/`on_failure` to format a resolved value or error into that pipeline's
+/// result text.
+pub async fn run_tool_calls<I>(
+    attempts: Vec<ToolCallAttempt<I>>,
+    on_success: impl Fn(&I, &str) -> String,
+    on_failure: impl Fn(&I, &str) -> String,
+) -> String {
+    let mut slots: Vec<Option<String>> = Vec::with_capacity(attempts.len());
+    let mut runnable: Vec<(usize, I, Arc<dyn Tool>, Value)> = Vec::new();
+
+    for attempt in attempts {
+        let slot_index = slots.len();
+        match attempt {
+            ToolCallAttempt::Ready(id, tool, arguments) => {
+                slots.push(None);
+                runnable.push((slot_index, id, tool, arguments));
+            }
+            ToolCallAttempt::Failed(id, reason) => {
+                slots.push(Some(on_failure(&id, &reason)));
+            }
+        }
+    }
+
+    let run_results = futures::future::join_all(
+        runnable
+            .iter()
+            .map(|(_, _, tool, arguments)| tool.run(arguments.clone())),
+    )
+    .await;
+
+    for ((slot_index, id, _, _), result) in runnable.into_iter().zip(run_results) {
+        slots[slot_index] = Some(match result {
+            Ok(value) => on_success(&id, &value),
+            Err(e) => on_failure(&id, &OllamaError::from(e).to_string()),
+        });
+    }
+
+    slots
+        .into_iter()
+        .map(|slot| slot.expect("every tool call produces an outcome"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" },
+            },
+        })
+    }
+
+    #[test]
+    fn validate_tool_arguments_rejects_missing_required_field() {
+        let result = validate_tool_arguments(&person_schema(), &json!({ "age": 30 }));
+        let error = result.unwrap_err();
+        assert!(error.contains("missing required field(s): name"), "{error}");
+    }
+
+    #[test]
+    fn validate_tool_arguments_rejects_unexpected_field() {
+        let result = validate_tool_arguments(
+            &person_schema(),
+            &json!({ "name": "Ada", "nickname": "Countess" }),
+        );
+        let error = result.unwrap_err();
+        assert!(error.contains("unexpected field(s): nickname"), "{error}");
+    }
+
+    #[test]
+    fn validate_tool_arguments_rejects_type_mismatch() {
+        let result =
+            validate_tool_arguments(&person_schema(), &json!({ "name": "Ada", "age": "thirty" }));
+        let error = result.unwrap_err();
+        assert!(
+            error.contains("'age' should be of type 'integer'"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn validate_tool_arguments_accepts_valid_arguments() {
+        let result = validate_tool_arguments(&person_schema(), &json!({ "name": "Ada", "age": 30 }));
+        assert!(result.is_ok());
+    }
 }
@@ -1,13 +1,17 @@
 use crate::error::OllamaError;
 use crate::generation::chat::{ChatMessage, ChatMessageResponse};
 use crate::generation::functions::pipelines::tiny_agent::DEFAULT_SYSTEM_TEMPLATE;
-use crate::generation::functions::pipelines::RequestParserBase;
+use crate::generation::functions::pipelines::{
+    run_tool_calls, validate_tool_arguments, RequestParserBase, ToolCallAttempt, ToolChoice,
+};
 use crate::generation::functions::tools::Tool;
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::Arc;
 
 pub fn convert_to_openai_tool(tool: &Arc<dyn Tool>) -> Value {
@@ -67,19 +71,47 @@ impl TinyFunctionCall {
         format!("<tool_response>\n{}\n</tool_response>\n", function_response)
     }
 
-    pub fn extract_tool_call(&self, content: &str) -> Option<String> {
+    pub fn extract_tool_calls(&self, content: &str) -> Vec<String> {
         let re = Regex::new(r"(?s)<tool_call>(.*?)</tool_call>").unwrap();
-        if let Some(captures) = re.captures(content) {
-            if let Some(matched) = captures.get(1) {
-                let result = matched
+        re.captures_iter(content)
+            .filter_map(|captures| captures.get(1))
+            .map(|matched| {
+                matched
                     .as_str()
                     .replace('\n', "")
                     .replace("{{", "{")
-                    .replace("}}", "}");
-                return Some(result);
-            }
+                    .replace("}}", "}")
+            })
+            .collect()
+    }
+
+    fn format_error_message(&self, error: &str) -> String {
+        format!(
+            "<tool_response>\nThere was an error parsing function calls\n Here's the error stack trace: {}\nPlease call the function again with correct syntax</tool_response>",
+            error
+        )
+    }
+
+    /// Validates and dispatches a single already-finalized tool call, used by `parse_stream` once
+    /// a `<tool_call>` block's arguments have been fully assembled and parsed.
+    async fn run_tool_call(
+        &self,
+        tools: &[Arc<dyn Tool>],
+        response: TinyFunctionCallSignature,
+    ) -> String {
+        match tools.iter().find(|t| t.name() == response.name) {
+            Some(tool) => match validate_tool_arguments(&tool.parameters(), &response.arguments) {
+                Ok(()) => match tool.run(response.arguments).await {
+                    Ok(result) => self.format_tool_response(&result),
+                    Err(e) => self.format_error_message(&OllamaError::from(e).to_string()),
+                },
+                Err(reason) => self.format_error_message(&format!(
+                    "Arguments for '{}' do not match its schema: {}",
+                    response.name, reason
+                )),
+            },
+            None => self.format_error_message(&format!("Tool name not found: {}", response.name)),
         }
-        None
     }
 }
 
@@ -90,40 +122,146 @@ impl RequestParserBase for TinyFunctionCall {
         input: &str,
         model_name: String,
         tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
     ) -> Result<ChatMessageResponse, ChatMessageResponse> {
-        //Extract between <tool_call> and </tool_call>
-        let tool_response = self.extract_tool_call(input);
-        match tool_response {
-            Some(tool_response_str) => {
-                let response_value: Result<TinyFunctionCallSignature, serde_json::Error> =
-                    serde_json::from_str(&tool_response_str);
-                match response_value {
-                    Ok(response) => {
-                        if let Some(tool) = tools.iter().find(|t| t.name() == response.name) {
-                            let tool_params = response.arguments;
-                            let result = self
-                                .function_call_with_history(
-                                    model_name.clone(),
-                                    tool_params.clone(),
+        //Extract every <tool_call>...</tool_call> block so a single turn can fire several tools
+        let tool_calls = self.extract_tool_calls(input);
+        if tool_calls.is_empty() {
+            return Err(self.error_handler(OllamaError::from(
+                "Error while extracting <tool_call> tags.".to_string(),
+            )));
+        }
+
+        if *tool_choice == ToolChoice::None {
+            return Err(self.error_handler(OllamaError::from(
+                "Tool calls are disabled, but the model attempted one.".to_string(),
+            )));
+        }
+
+        let mut attempts: Vec<ToolCallAttempt<()>> = Vec::with_capacity(tool_calls.len());
+        for tool_call_str in &tool_calls {
+            match serde_json::from_str::<TinyFunctionCallSignature>(tool_call_str) {
+                Ok(response) => {
+                    if let ToolChoice::Required(required_name) = tool_choice {
+                        if &response.name != required_name {
+                            return Err(self.error_handler(OllamaError::from(format!(
+                                "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                                required_name, response.name, required_name
+                            ))));
+                        }
+                    }
+                    match tools.iter().find(|t| t.name() == response.name) {
+                        Some(tool) => {
+                            match validate_tool_arguments(&tool.parameters(), &response.arguments) {
+                                Ok(()) => attempts.push(ToolCallAttempt::Ready(
+                                    (),
                                     tool.clone(),
-                                )
-                                .await?; //Error is also returned as String for LLM feedback
-                            return Ok(result);
-                        } else {
-                            return Err(self.error_handler(OllamaError::from(
-                                "Tool name not found".to_string(),
-                            )));
+                                    response.arguments,
+                                )),
+                                Err(reason) => attempts.push(ToolCallAttempt::Failed(
+                                    (),
+                                    format!(
+                                        "Arguments for '{}' do not match its schema: {}",
+                                        response.name, reason
+                                    ),
+                                )),
+                            }
                         }
+                        None => attempts.push(ToolCallAttempt::Failed(
+                            (),
+                            format!("Tool name not found: {}", response.name),
+                        )),
                     }
-                    Err(e) => return Err(self.error_handler(OllamaError::from(e))),
                 }
+                Err(e) => attempts.push(ToolCallAttempt::Failed((), e.to_string())),
+            }
+        }
+
+        let combined = run_tool_calls(
+            attempts,
+            |_, value| self.format_tool_response(value),
+            |_, error| self.format_error_message(error),
+        )
+        .await;
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(combined)),
+            done: true,
+            final_data: None,
+        })
+    }
+
+    async fn parse_stream(
+        &self,
+        mut stream: Pin<Box<dyn Stream<Item = ChatMessageResponse> + Send>>,
+        model_name: String,
+        tools: Vec<Arc<dyn Tool>>,
+        tool_choice: &ToolChoice,
+    ) -> Result<ChatMessageResponse, ChatMessageResponse> {
+        let mut buffer = String::new();
+        let mut finalized = 0usize;
+        let mut responses = Vec::new();
+
+        while let Some(chunk) = stream.next().await {
+            if let Some(message) = &chunk.message {
+                buffer.push_str(&message.content);
+            }
+
+            // Tags that have fully closed are ready to finalize; anything still open is a
+            // partial delta and is left in the buffer until the next chunk completes it.
+            let closed_calls = self.extract_tool_calls(&buffer);
+            while finalized < closed_calls.len() {
+                let arguments_buffer = &closed_calls[finalized];
+                responses.push(
+                    match serde_json::from_str::<TinyFunctionCallSignature>(arguments_buffer) {
+                        Ok(response) => {
+                            if *tool_choice == ToolChoice::None {
+                                self.format_error_message(&format!(
+                                    "Tool calls are disabled, but a call to '{}' was attempted.",
+                                    response.name
+                                ))
+                            } else if let ToolChoice::Required(required_name) = tool_choice {
+                                if response.name != *required_name {
+                                    self.format_error_message(&format!(
+                                        "You must call the '{}' tool, but called '{}' instead. Please call '{}' again.",
+                                        required_name, response.name, required_name
+                                    ))
+                                } else {
+                                    self.run_tool_call(&tools, response).await
+                                }
+                            } else {
+                                self.run_tool_call(&tools, response).await
+                            }
+                        }
+                        Err(e) => self.format_error_message(&format!(
+                            "The assembled tool call arguments were not valid JSON: {}",
+                            e
+                        )),
+                    },
+                );
+                finalized += 1;
             }
-            None => {
-                return Err(self.error_handler(OllamaError::from(
-                    "Error while extracting <tool_call> tags.".to_string(),
-                )))
+
+            if chunk.done {
+                break;
             }
         }
+
+        if responses.is_empty() {
+            return Err(self.error_handler(OllamaError::from(
+                "Stream ended before a complete <tool_call> block was assembled".to_string(),
+            )));
+        }
+
+        Ok(ChatMessageResponse {
+            model: model_name,
+            created_at: "".to_string(),
+            message: Some(ChatMessage::assistant(responses.join("\n"))),
+            done: true,
+            final_data: None,
+        })
     }
 
     fn format_query(&self, input: &str) -> String {
@@ -137,18 +275,34 @@ impl RequestParserBase for TinyFunctionCall {
         format!("Agent iteration to assist with user query: {}", response)
     }
 
-    async fn get_system_message(&self, tools: &[Arc<dyn Tool>]) -> ChatMessage {
-        let tools_info: Vec<Value> = tools.iter().map(convert_to_openai_tool).collect();
+    async fn get_system_message(&self, tools: &[Arc<dyn Tool>], tool_choice: &ToolChoice) -> ChatMessage {
+        if *tool_choice == ToolChoice::None {
+            return ChatMessage::system(
+                "Answer the user directly in plain text. Do not call any tools.".to_string(),
+            );
+        }
+
+        let tools_info: Vec<Value> = match tool_choice {
+            ToolChoice::Required(name) => tools
+                .iter()
+                .filter(|t| &t.name() == name)
+                .map(convert_to_openai_tool)
+                .collect(),
+            _ => tools.iter().map(convert_to_openai_tool).collect(),
+        };
         let tools_json = serde_json::to_string(&tools_info).unwrap();
-        let system_message_content = DEFAULT_SYSTEM_TEMPLATE.replace("{tools}", &tools_json);
+        let mut system_message_content = DEFAULT_SYSTEM_TEMPLATE.replace("{tools}", &tools_json);
+        if let ToolChoice::Required(name) = tool_choice {
+            system_message_content.push_str(&format!(
+                "\nYou must call the '{}' tool to answer this request. No other tool is allowed.",
+                name
+            ));
+        }
         ChatMessage::system(system_message_content)
     }
 
     fn error_handler(&self, error: OllamaError) -> ChatMessageResponse {
-        let error_message = format!(
-            "<tool_response>\nThere was an error parsing function calls\n Here's the error stack trace: {}\nPlease call the function again with correct syntax</tool_response>",
-            error
-        );
+        let error_message = self.format_error_message(&error.to_string());
 
         ChatMessageResponse {
             model: "".to_string(),
@@ -158,4 +312,8 @@ impl RequestParserBase for TinyFunctionCall {
             final_data: None,
         }
     }
+
+    fn has_tool_call_attempt(&self, input: &str) -> bool {
+        !self.extract_tool_calls(input).is_empty()
+    }
 }